@@ -6,4 +6,10 @@
 fn main() {
 	// we don't need to rebuild for anything else
 	println!("cargo:rerun-if-changed=build.rs");
+
+	// Forward the target triple so it can be folded into the layout
+	// fingerprint via `env!("TARGET")`; cargo sets this for build scripts but
+	// not for the crate itself.
+	let target = std::env::var("TARGET").unwrap();
+	println!("cargo:rustc-env=TARGET={}", target);
 }
@@ -7,10 +7,15 @@
 //! * It is guaranteed to be identical within multiple invocations of the same
 //! binary.
 //! * It is guaranteed to be different across binaries with different code or
-//! data segments or layout.
+//! data segments or layout, whenever the executable can be read from disk.
 //! * Equality is unspecified if the binaries have identical code and data
 //! segments and layout but differ immaterially (e.g. if a timestamp is included
 //! in the binary at compile time).
+//! * Where the executable can't be read from disk (e.g. under Miri or on
+//! `wasm32`), only differences in target triple, pointer width, endianness or
+//! other ABI-relevant layout are guaranteed to produce distinct IDs -- two
+//! distinct programs built for the same target are *not* distinguished by
+//! this fallback.
 //!
 //! # Examples
 //!
@@ -29,6 +34,15 @@
 //! This looks first for linker-inserted build ID / binary UUIDs (i.e.
 //! `.note.gnu.build-id` on Linux; `LC_UUID` in Mach-O; etc), falling back to
 //! hashing the whole binary.
+//!
+//! Where the executable can be read from disk, [`get`] is already a
+//! deterministic name-based UUIDv5 over that data, reproducible by any tool
+//! that knows this crate's fixed namespace UUID. [`get_stable`] exists for
+//! callers who'd rather verify against a widely-available primitive
+//! (SHA-256) than reimplement UUIDv5 (SHA-1), and documents its exact byte
+//! layout for that purpose. Where the executable can't be read from disk
+//! (e.g. `wasm32`, under Miri), [`get`] instead falls back to an internal,
+//! non-reproducible hash, and [`get_stable`] returns an error.
 
 #![doc(html_root_url = "https://docs.rs/build_id/0.2.1")]
 #![warn(
@@ -44,11 +58,16 @@
 )] // from https://github.com/rust-unofficial/patterns/blob/master/anti_patterns/deny-warnings.md
 #![allow(clippy::must_use_candidate)]
 
+use object::Object;
 use once_cell::sync::Lazy;
+use sha2::{Digest, Sha256};
 use std::{
 	any::TypeId,
+	fs,
 	hash::{Hash, Hasher},
-	io,
+	io::{self, Read},
+	mem::{align_of, size_of},
+	path::Path,
 };
 use uuid::Uuid;
 
@@ -63,10 +82,15 @@ static BUILD_ID: Lazy<Uuid> = Lazy::new(calculate);
 /// * It is guaranteed to be identical within multiple invocations of the same
 /// binary.
 /// * It is guaranteed to be different across binaries with different code or
-/// data segments or layout.
+/// data segments or layout, whenever the executable can be read from disk.
 /// * Equality is unspecified if the binaries have identical code and data
 /// segments and layout but differ immaterially (e.g. if a timestamp is included
 /// in the binary at compile time).
+/// * Where the executable can't be read from disk (e.g. under Miri or on
+/// `wasm32`), only differences in target triple, pointer width, endianness or
+/// other ABI-relevant layout are guaranteed to produce distinct IDs -- two
+/// distinct programs built for the same target are *not* distinguished by
+/// this fallback.
 ///
 /// # Examples
 ///
@@ -90,28 +114,139 @@ pub fn get() -> Uuid {
 	*BUILD_ID
 }
 
-#[allow(clippy::needless_pass_by_value)]
-fn from_header<H: Hasher>(_hasher: H) -> Result<H, ()> {
-	// LC_UUID https://opensource.apple.com/source/libsecurity_codesigning/libsecurity_codesigning-55037.6/lib/machorep.cpp https://stackoverflow.com/questions/10119700/how-to-get-mach-o-uuid-of-a-running-process
-	// .note.gnu.build-id https://github.com/golang/go/issues/21564 https://github.com/golang/go/blob/178307c3a72a9da3d731fecf354630761d6b246c/src/cmd/go/internal/buildid/buildid.go
-	Err(())
+static STABLE_BUILD_ID: Lazy<Option<Uuid>> = Lazy::new(|| {
+	canonical_bytes().ok().map(|bytes| {
+		let digest = Sha256::digest(&bytes);
+		let mut uuid_bytes = [0; 16];
+		uuid_bytes.copy_from_slice(&digest[..16]);
+		uuid::Builder::from_bytes(uuid_bytes)
+			.with_variant(uuid::Variant::RFC4122)
+			.into_uuid()
+	})
+});
+
+/// Returns a [`Uuid`] derived from a SHA-256 hash of a precisely documented
+/// byte stream, reproducible by any external tool that can compute SHA-256,
+/// without the UUIDv5 construction and [`NAMESPACE`] that [`get`] uses.
+///
+/// # Algorithm
+///
+/// The byte stream hashed is the linker-inserted build ID record (see
+/// [`get`]'s "Note" section) if one is present in the current executable;
+/// otherwise it is the executable's file contents in their entirety. This is
+/// hashed with SHA-256, and the first 16 bytes of the resulting digest
+/// (taken in the order SHA-256 emits them) become the [`Uuid`]'s bytes. Only
+/// the variant bits are forced, to RFC 4122, for structural validity; the
+/// version nibble is left as whatever the digest happens to contain and
+/// doesn't claim any UUID version, since a truncated SHA-256 digest isn't
+/// one of the name-based constructions RFC 4122 defines (those are MD5/v3
+/// and SHA-1/v5). Any tool able to locate the same bytes (e.g. `readelf
+/// -n`/`.note.gnu.build-id`, or the whole file) and run `sha256sum` can
+/// reproduce it.
+///
+/// Like [`get`], this is computed once per process and cached.
+///
+/// # Errors
+///
+/// Returns an error if no such byte stream is available, e.g. on `wasm32` or
+/// under Miri, where the current executable can't be read from disk.
+///
+/// See also [`algorithm_version`], which callers can use to detect future
+/// changes to this algorithm.
+pub fn get_stable() -> io::Result<Uuid> {
+	STABLE_BUILD_ID.ok_or_else(|| io::Error::new(io::ErrorKind::Other, "no stable build ID source available on this target"))
 }
-fn from_exe<H: Hasher>(mut hasher: H) -> Result<H, ()> {
+
+/// Returns the version of the algorithm used by [`get_stable`].
+///
+/// This is bumped whenever the byte stream or hash construction fed into
+/// [`get_stable`] changes, so that consumers comparing build IDs produced by
+/// different versions of this crate can detect the format change rather than
+/// silently comparing incompatible values.
+#[inline]
+pub fn algorithm_version() -> u32 {
+	1
+}
+
+/// The byte stream [`get_stable`] hashes: the linker-inserted build ID if
+/// present, otherwise the whole executable file.
+fn canonical_bytes() -> Result<Vec<u8>, ()> {
 	#[cfg(not(target_arch = "wasm32"))]
 	{
 		if cfg!(miri) {
 			return Err(());
 		}
 		let file = palaver::env::exe().map_err(drop)?;
-		let _ = io::copy(&mut &file, &mut HashWriter(&mut hasher)).map_err(drop)?;
-		Ok(hasher)
+		let mut data = Vec::new();
+		let _ = io::copy(&mut &file, &mut data).map_err(drop)?;
+		Ok(header_or_full_bytes(data))
 	}
 	#[cfg(target_arch = "wasm32")]
 	{
-		let _ = &mut hasher;
 		Err(())
 	}
 }
+
+/// The linker-inserted build ID within `data` if present, else `data` itself.
+fn header_or_full_bytes(data: Vec<u8>) -> Vec<u8> {
+	header_build_id(&data).unwrap_or(data)
+}
+
+/// Returns a [`Uuid`] for the build of the binary at `path`, computed the
+/// same way as [`get_stable`]'s byte stream (the linker-inserted build ID if
+/// present, otherwise the whole file) but name-hashed as a UUIDv5 the same
+/// way as [`get`], letting a caller compare a candidate binary on disk
+/// against a running process without having to exec it first.
+///
+/// This only agrees with a running process's [`get`] on targets where the
+/// executable can be read from disk: this function always reads `path` and
+/// so always produces the name-based result, but [`get`] falls back to a
+/// different, non-reproducible value when it can't read its own executable
+/// (e.g. `wasm32`, under Miri). On those targets, a path-based ID from here
+/// will never match [`get`]'s.
+///
+/// # Errors
+///
+/// Returns an error if `path` can't be read.
+pub fn get_for_path(path: impl AsRef<Path>) -> io::Result<Uuid> {
+	let data = fs::read(path)?;
+	Ok(Uuid::new_v5(&NAMESPACE, &header_or_full_bytes(data)))
+}
+
+/// As [`get_for_path`], but reads the binary from an arbitrary [`Read`]
+/// rather than a path, for callers that already have the bytes in hand
+/// (e.g. received over the network).
+///
+/// # Errors
+///
+/// Returns an error if `reader` can't be read to completion.
+pub fn get_for_reader(mut reader: impl Read) -> io::Result<Uuid> {
+	let mut data = Vec::new();
+	let _ = reader.read_to_end(&mut data)?;
+	Ok(Uuid::new_v5(&NAMESPACE, &header_or_full_bytes(data)))
+}
+
+// LC_UUID https://opensource.apple.com/source/libsecurity_codesigning/libsecurity_codesigning-55037.6/lib/machorep.cpp https://stackoverflow.com/questions/10119700/how-to-get-mach-o-uuid-of-a-running-process
+// .note.gnu.build-id https://github.com/golang/go/issues/21564 https://github.com/golang/go/blob/178307c3a72a9da3d731fecf354630761d6b246c/src/cmd/go/internal/buildid/buildid.go
+//
+// Extracts the linker-inserted build ID from a parsed object file, if present:
+// the ELF `NT_GNU_BUILD_ID` note, the Mach-O `LC_UUID` load command, or the PE
+// CodeView/RSDS debug directory entry.
+fn header_build_id(data: &[u8]) -> Option<Vec<u8>> {
+	let object = object::File::parse(data).ok()?;
+	if let Ok(Some(id)) = object.build_id() {
+		return Some(id.to_vec());
+	}
+	if let Ok(Some(uuid)) = object.mach_uuid() {
+		return Some(uuid.to_vec());
+	}
+	if let Ok(Some(pdb)) = object.pdb_info() {
+		let mut id = pdb.guid().to_vec();
+		id.extend_from_slice(&pdb.age().to_le_bytes());
+		return Some(id);
+	}
+	None
+}
 fn from_type_id<H: Hasher>(mut hasher: H) -> Result<H, ()> {
 	fn type_id_of<T: 'static>(_: &T) -> TypeId {
 		TypeId::of::<T>()
@@ -125,13 +260,61 @@ fn from_type_id<H: Hasher>(mut hasher: H) -> Result<H, ()> {
 	Ok(hasher)
 }
 
+/// Hashes in a fingerprint of the binary's machine layout: the target
+/// triple, pointer width, endianness, and the size/alignment of a handful of
+/// representative types.
+///
+/// [`from_type_id`]'s `TypeId`s are a proxy for "identical binary layout",
+/// but a fragile one: the same source compiled for two different target
+/// triples, or with different `cfg`s that change representation, can
+/// produce the same `TypeId`s. Folding this in distinguishes those cases.
+///
+/// Note this only distinguishes *targets*/layouts, not distinct consuming
+/// programs: on `wasm32` or under Miri, where [`canonical_bytes`] can't read
+/// the executable at all, two different programs built for the same target
+/// with the same version of this crate still collapse to the same ID -- this
+/// fingerprint doesn't see the consumer's own code or data, only this
+/// crate's compilation and the target it's compiled for.
+fn layout_fingerprint<H: Hasher>(mut hasher: H) -> H {
+	fn size_align<T>() -> (usize, usize) {
+		(size_of::<T>(), align_of::<T>())
+	}
+
+	env!("TARGET").hash(&mut hasher);
+	cfg!(target_endian = "big").hash(&mut hasher);
+	size_align::<usize>().hash(&mut hasher);
+	size_align::<*const ()>().hash(&mut hasher);
+	size_align::<&[u8]>().hash(&mut hasher);
+	size_align::<Option<&()>>().hash(&mut hasher);
+	size_align::<u128>().hash(&mut hasher);
+
+	hasher
+}
+
+/// Namespace UUID for this crate's name-based (v5) build IDs:
+/// `6b8a9e2c-6c0a-4b8e-9b0a-9e6f6c9d6a2f`, generated once and fixed forever.
+///
+/// This is `pub` so that an independent implementation of UUIDv5 (SHA-1 over
+/// `NAMESPACE.as_bytes()` followed by the name bytes, per RFC 4122) can
+/// recompute [`get`]'s or [`get_for_path`]'s value without depending on this
+/// crate.
+pub const NAMESPACE: Uuid = Uuid::from_u128(0x6b8a_9e2c_6c0a_4b8e_9b0a_9e6f_6c9d_6a2f);
+
 fn calculate() -> Uuid {
-	let hasher = twox_hash::XxHash::with_seed(0);
+	if let Ok(name) = canonical_bytes() {
+		// A proper name-based UUID: deterministic, and reproducible by any
+		// other implementation of UUIDv5 given the same namespace and bytes,
+		// unlike stamping an arbitrary hash with a Random version tag.
+		return Uuid::new_v5(&NAMESPACE, &name);
+	}
 
-	let hasher = from_header(hasher)
-		.or_else(|()| from_exe(hasher))
-		.unwrap_or(hasher);
-	let mut hasher = from_type_id(hasher).unwrap();
+	// No linker build ID and no readable executable (e.g. wasm32, under
+	// Miri): there's no stable "name" to hash, so fall back to whatever
+	// layout information is available, tagged as an opaque (not name-based)
+	// value.
+	let hasher = twox_hash::XxHash::with_seed(0);
+	let hasher = from_type_id(hasher).unwrap();
+	let mut hasher = layout_fingerprint(hasher);
 
 	let mut bytes = [0; 16];
 	<byteorder::NativeEndian as byteorder::ByteOrder>::write_u64(&mut bytes[..8], hasher.finish());
@@ -144,20 +327,6 @@ fn calculate() -> Uuid {
 		.into_uuid()
 }
 
-struct HashWriter<T: Hasher>(T);
-impl<T: Hasher> io::Write for HashWriter<T> {
-	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-		self.0.write(buf);
-		Ok(buf.len())
-	}
-	fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
-		self.write(buf).map(|_| ())
-	}
-	fn flush(&mut self) -> io::Result<()> {
-		Ok(())
-	}
-}
-
 #[cfg(test)]
 mod test {
 	use wasm_bindgen_test::wasm_bindgen_test;
@@ -179,4 +348,74 @@ mod test {
 		assert_eq!(x, super::get());
 		assert_eq!(x, super::get());
 	}
+
+	/// Builds a minimal synthetic ELF object containing an
+	/// `NT_GNU_BUILD_ID` note with the given descriptor bytes, independently
+	/// of [super::header_build_id], so tests can assert the extraction
+	/// actually reads what was written rather than just agreeing with
+	/// itself.
+	fn synthetic_elf_with_build_id(desc: &[u8]) -> Vec<u8> {
+		use object::{
+			write::Object as WriteObject, Architecture, BinaryFormat, Endianness, SectionKind
+		};
+
+		let mut obj = WriteObject::new(BinaryFormat::Elf, Architecture::X86_64, Endianness::Little);
+		let section = obj.add_section(Vec::new(), b".note.gnu.build-id".to_vec(), SectionKind::Note);
+
+		// ELF note layout: namesz, descsz, type, name (NUL-terminated, padded
+		// to 4 bytes), descriptor.
+		let name = b"GNU\0";
+		let mut note = Vec::new();
+		note.extend_from_slice(&(name.len() as u32).to_le_bytes());
+		note.extend_from_slice(&(desc.len() as u32).to_le_bytes());
+		note.extend_from_slice(&3u32.to_le_bytes()); // NT_GNU_BUILD_ID
+		note.extend_from_slice(name);
+		note.extend_from_slice(desc);
+		obj.set_section_data(section, note, 4);
+
+		obj.write().unwrap()
+	}
+
+	#[test]
+	fn header_build_id_reads_elf_note() {
+		let desc = b"deadbeefcafef00d01234567";
+		let bytes = synthetic_elf_with_build_id(desc);
+		assert_eq!(super::header_build_id(&bytes).as_deref(), Some(&desc[..]));
+	}
+
+	// get_stable() and get_for_path() read the current executable from disk,
+	// which isn't possible on wasm32 or under Miri.
+	#[test]
+	#[cfg(not(any(target_arch = "wasm32", miri)))]
+	fn stable() {
+		let x = super::get_stable().unwrap();
+		for _ in 0..1000 {
+			assert_eq!(x, super::get_stable().unwrap());
+		}
+	}
+
+	#[test]
+	#[cfg(not(any(target_arch = "wasm32", miri)))]
+	fn for_path() {
+		let path = std::env::current_exe().unwrap();
+		assert_eq!(super::get_for_path(path).unwrap(), super::get());
+	}
+
+	// Independent of header_build_id: computes the expected UUID directly
+	// from the descriptor bytes we wrote in, so this would fail if
+	// get_for_path/get_for_reader silently fell back to hashing the whole
+	// synthetic file instead of extracting the build ID note.
+	#[test]
+	fn for_synthetic_build_id() {
+		let desc = b"f00dfeed00112233deadbeef";
+		let bytes = synthetic_elf_with_build_id(desc);
+		let expected = uuid::Uuid::new_v5(&super::NAMESPACE, desc);
+
+		let path = std::env::temp_dir().join(format!("build_id_test_{}", std::process::id()));
+		std::fs::write(&path, &bytes).unwrap();
+		assert_eq!(super::get_for_path(&path).unwrap(), expected);
+		std::fs::remove_file(&path).unwrap();
+
+		assert_eq!(super::get_for_reader(&bytes[..]).unwrap(), expected);
+	}
 }